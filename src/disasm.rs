@@ -0,0 +1,48 @@
+//! x64 disassembly of JIT-generated code, annotated with the `BfIR` op
+//! that produced each instruction. Only built with the `disasm` feature
+//! so the default build doesn't pull in a disassembler dependency.
+
+use alloc::{string::String, vec::Vec};
+
+use iced_x86::{Decoder, DecoderOptions, Formatter, Instruction, NasmFormatter};
+
+use crate::ir::BfIR;
+
+pub struct AnnotatedInsn {
+    pub address: u64,
+    pub text: String,
+    /// The `BfIR` op this instruction was emitted for, if any (the
+    /// prologue has none). Code emitted after the last op (the epilogue and
+    /// the trampolines) is tagged with that last op.
+    pub ir: Option<BfIR>,
+}
+
+/// Disassemble `code` (loaded at `base`), tagging each instruction with the
+/// `BfIR` op that was being emitted at its starting offset. `boundaries`
+/// must be sorted by offset, as returned by `VM::compile_annotated`.
+pub fn disassemble(code: &[u8], base: u64, boundaries: &[(usize, BfIR)]) -> Vec<AnnotatedInsn> {
+    let mut decoder = Decoder::with_ip(64, code, base, DecoderOptions::NONE);
+    let mut formatter = NasmFormatter::new();
+    let mut insn = Instruction::default();
+    let mut out = Vec::new();
+
+    while decoder.can_decode() {
+        decoder.decode_out(&mut insn);
+        let offset = (insn.ip() - base) as usize;
+        let ir = boundaries
+            .iter()
+            .rev()
+            .find(|&&(start, _)| start <= offset)
+            .map(|&(_, ir)| ir);
+
+        let mut text = String::new();
+        formatter.format(&insn, &mut text);
+        out.push(AnnotatedInsn {
+            address: insn.ip(),
+            text,
+            ir,
+        });
+    }
+
+    out
+}
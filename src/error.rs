@@ -1,31 +1,42 @@
-use std::fmt::Display;
+use core::fmt::Display;
+
+use crate::io::IoError;
 
 #[derive(Debug)]
 pub enum RuntimeError {
-    IO(std::io::Error),
+    IO(IoError),
     PointerOverflow,
+    /// The instruction budget passed via `--max-steps` (or the embedder's
+    /// equivalent) hit zero before the program finished.
+    FuelExhausted,
+    /// The wall-clock deadline passed to the runner elapsed before the
+    /// program finished.
+    TimedOut,
 }
 
 impl Display for RuntimeError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             RuntimeError::IO(io) => write!(f, "IO: {}", io),
             RuntimeError::PointerOverflow => write!(f, "Pointer overflow"),
+            RuntimeError::FuelExhausted => write!(f, "Instruction budget exhausted"),
+            RuntimeError::TimedOut => write!(f, "Execution timed out"),
         }
     }
 }
 
+#[cfg(feature = "std")]
 impl std::error::Error for RuntimeError {}
 
 #[derive(Debug)]
 pub enum VMError {
-    IO(std::io::Error),
+    IO(IoError),
     Compile(crate::ir::CompileError),
     Runtime(RuntimeError),
 }
 
 impl Display for VMError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             VMError::IO(err) => write!(f, "IO: {}", err),
             VMError::Compile(err) => write!(f, "Compile: {}", err),
@@ -40,8 +51,8 @@ impl From<RuntimeError> for VMError {
     }
 }
 
-impl From<std::io::Error> for VMError {
-    fn from(value: std::io::Error) -> Self {
+impl From<IoError> for VMError {
+    fn from(value: IoError) -> Self {
         VMError::IO(value)
     }
 }
@@ -52,6 +63,7 @@ impl From<crate::ir::CompileError> for VMError {
     }
 }
 
+#[cfg(feature = "std")]
 impl std::error::Error for VMError {}
 
-pub type Result<T> = std::result::Result<T, VMError>;
+pub type Result<T> = core::result::Result<T, VMError>;
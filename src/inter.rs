@@ -1,101 +1,173 @@
-use std::{
-    io::{Read, Write},
-    path::Path,
-    slice,
-};
-
-use crate::{
-    error::{self, Result, RuntimeError},
-    ir::{self, BfIR},
-};
-
-pub struct Interpreter {
-    pc: u32,
-    ptr: u32,
-    code: Vec<BfIR>,
-    memory: Box<[u8]>,
-    input: Box<dyn Read>,
-    output: Box<dyn Write>,
-}
-
-const MEM_SIZE: usize = 4 * 1024 * 1024;
-
-impl Interpreter {
-    pub fn new(
-        path: &Path,
-        input: Box<dyn Read>,
-        output: Box<dyn Write>,
-        optimize: bool,
-    ) -> Result<Self> {
-        let src = std::fs::read_to_string(path)?;
-        let mut ir = ir::compile(&src)?;
-        drop(src);
-
-        if optimize {
-            ir::optimize_ir(&mut ir);
-        }
-        let memory = vec![0; MEM_SIZE].into_boxed_slice();
-        Ok(Self {
-            pc: 0,
-            ptr: 0,
-            code: ir,
-            memory,
-            input,
-            output,
-        })
-    }
-
-    pub fn run(&mut self) -> Result<()> {
-        let code_len = self.code.len() as u32;
-        while self.pc < code_len {
-            // println!("Code: {:?}, PC: {}", self.code[self.pc], self.pc);
-            match &self.code[self.pc as usize] {
-                BfIR::AddVal(x) => {
-                    self.memory[self.ptr as usize] = self.memory[self.ptr as usize].wrapping_add(*x)
-                }
-                BfIR::SubVal(x) => {
-                    self.memory[self.ptr as usize] = self.memory[self.ptr as usize].wrapping_sub(*x)
-                }
-                BfIR::AddPtr(x) => {
-                    // len < ptr + x
-                    if self.memory.len() as u32 - self.ptr <= *x {
-                        return Err(error::VMError::Runtime(RuntimeError::PointerOverflow));
-                    }
-                    self.ptr = self.ptr + *x;
-                }
-                BfIR::SubPtr(x) => {
-                    if self.ptr < *x {
-                        return Err(error::VMError::Runtime(RuntimeError::PointerOverflow));
-                    }
-                    self.ptr -= x;
-                }
-                BfIR::GetByte => {
-                    let mut buf = [0_u8];
-                    match self.input.read(&mut buf) {
-                        Ok(0) => (),
-                        Ok(1) => self.memory[self.ptr as usize] = buf[0],
-                        Err(e) => return Err(error::VMError::IO(e)),
-                        _ => unreachable!(),
-                    }
-                }
-                BfIR::PutByte => {
-                    let val = self.memory[self.ptr as usize];
-                    match self.output.write_all(slice::from_ref(&val)) {
-                        Ok(_) => (),
-                        Err(e) => return Err(error::VMError::IO(e)),
-                    }
-                }
-                BfIR::Jz(pos) => {
-                    if self.memory[self.ptr as usize] == 0 {
-                        self.pc = *pos;
-                    }
-                }
-                BfIR::Jnz(pos) => {
-                    self.pc = *pos - 1;
-                }
-            }
-            self.pc += 1;
-        }
-        Ok(())
-    }
-}
+use alloc::{boxed::Box, vec, vec::Vec};
+use core::slice;
+
+use crate::{
+    error::{self, Result, RuntimeError},
+    io::{self, Read, Write},
+    ir::{self, BfIR},
+    tape::{Memory, PagedTape},
+};
+#[cfg(feature = "std")]
+use crate::tape::Tape;
+
+pub struct Interpreter<'a> {
+    pc: u32,
+    ptr: i64,
+    code: Vec<BfIR>,
+    memory: Memory<'a>,
+    input: Box<dyn Read>,
+    output: Box<dyn Write>,
+    // Remaining instruction budget; `None` means unbounded.
+    fuel: Option<u64>,
+    // Periodic hook polled once per executed IR op; returning `true` aborts
+    // the run with `RuntimeError::TimedOut`. Lets callers implement a
+    // wall-clock timeout (or any other cooperative cutoff) without the
+    // interpreter core depending on `std::time`.
+    tick: Option<Box<dyn FnMut() -> bool + 'a>>,
+}
+
+#[cfg(feature = "std")]
+const MEM_SIZE: usize = 4 * 1024 * 1024;
+
+impl<'a> Interpreter<'a> {
+    #[cfg(feature = "std")]
+    pub fn new(
+        path: &std::path::Path,
+        input: Box<dyn Read>,
+        output: Box<dyn Write>,
+        optimize: bool,
+        max_steps: Option<u64>,
+    ) -> Result<Self> {
+        let src = std::fs::read_to_string(path)?;
+        let memory = Memory::Fixed(Tape::Owned(vec![0; MEM_SIZE].into_boxed_slice()));
+        Self::from_source(&src, memory, input, output, optimize, max_steps)
+    }
+
+    /// Build an interpreter over a caller-supplied memory (a fixed tape or
+    /// a [`PagedTape`]); see [`Memory`].
+    pub fn from_source(
+        src: &str,
+        memory: Memory<'a>,
+        input: Box<dyn Read>,
+        output: Box<dyn Write>,
+        optimize: bool,
+        max_steps: Option<u64>,
+    ) -> Result<Self> {
+        let mut ir = ir::compile(src)?;
+
+        if optimize {
+            ir::optimize_ir(&mut ir);
+        }
+        Ok(Self {
+            pc: 0,
+            ptr: 0,
+            code: ir,
+            memory,
+            input: Box::new(io::BufReader::new(input)),
+            output: Box::new(io::BufWriter::new(output)),
+            fuel: max_steps,
+            tick: None,
+        })
+    }
+
+    /// Build an interpreter whose tape grows on demand instead of being a
+    /// fixed size; see [`PagedTape`].
+    pub fn with_paged_tape(
+        src: &str,
+        page_size: usize,
+        max_pages: usize,
+        input: Box<dyn Read>,
+        output: Box<dyn Write>,
+        optimize: bool,
+        max_steps: Option<u64>,
+    ) -> Result<Self> {
+        let memory = Memory::Paged(PagedTape::new(page_size, max_pages));
+        Self::from_source(src, memory, input, output, optimize, max_steps)
+    }
+
+    /// Install a callback polled once per executed IR op; returning `true`
+    /// aborts the run with `RuntimeError::TimedOut`.
+    pub fn set_tick<F: FnMut() -> bool + 'a>(&mut self, tick: F) {
+        self.tick = Some(Box::new(tick));
+    }
+
+    pub fn run(&mut self) -> Result<()> {
+        let code_len = self.code.len() as u32;
+        while self.pc < code_len {
+            if let Some(fuel) = &mut self.fuel {
+                if *fuel == 0 {
+                    return Err(error::VMError::Runtime(RuntimeError::FuelExhausted));
+                }
+                *fuel -= 1;
+            }
+            if let Some(tick) = &mut self.tick {
+                if tick() {
+                    return Err(error::VMError::Runtime(RuntimeError::TimedOut));
+                }
+            }
+            // println!("Code: {:?}, PC: {}", self.code[self.pc], self.pc);
+            match &self.code[self.pc as usize] {
+                BfIR::AddVal(x) => {
+                    let cell = self.memory.get_mut(self.ptr)?;
+                    *cell = cell.wrapping_add(*x);
+                }
+                BfIR::SubVal(x) => {
+                    let cell = self.memory.get_mut(self.ptr)?;
+                    *cell = cell.wrapping_sub(*x);
+                }
+                BfIR::AddPtr(x) => self.ptr = self.memory.move_ptr(self.ptr, *x as i64)?,
+                BfIR::SubPtr(x) => self.ptr = self.memory.move_ptr(self.ptr, -(*x as i64))?,
+                BfIR::GetByte => {
+                    // Flush any buffered output before blocking on input, so
+                    // a prompt the program just wrote is actually visible.
+                    self.output.flush().map_err(error::VMError::IO)?;
+                    let mut buf = [0_u8];
+                    match self.input.read(&mut buf) {
+                        Ok(0) => (),
+                        Ok(1) => self.memory.set(self.ptr, buf[0])?,
+                        Err(e) => return Err(error::VMError::IO(e)),
+                        _ => unreachable!(),
+                    }
+                }
+                BfIR::PutByte => {
+                    let val = self.memory.get(self.ptr);
+                    match self.output.write_all(slice::from_ref(&val)) {
+                        Ok(_) => (),
+                        Err(e) => return Err(error::VMError::IO(e)),
+                    }
+                }
+                BfIR::Jz(pos) => {
+                    if self.memory.get(self.ptr) == 0 {
+                        self.pc = *pos;
+                    }
+                }
+                BfIR::Jnz(pos) => {
+                    self.pc = *pos - 1;
+                }
+                BfIR::SetVal(v) => self.memory.set(self.ptr, *v)?,
+                BfIR::AddMul { offset, factor } => {
+                    let add = self.memory.get(self.ptr).wrapping_mul(*factor);
+                    // Route through `move_ptr` instead of computing the
+                    // target directly, so a fixed tape rejects an
+                    // out-of-range offset with `PointerOverflow` (same as
+                    // `AddPtr`/`SubPtr`) rather than panicking on the
+                    // `get_mut` index below.
+                    let target = self.memory.move_ptr(self.ptr, *offset as i64)?;
+                    let cell = self.memory.get_mut(target)?;
+                    *cell = cell.wrapping_add(add);
+                }
+                BfIR::PutRepeat { count } => {
+                    let val = self.memory.get(self.ptr);
+                    let buf = vec![val; *count as usize];
+                    match self.output.write_all(&buf) {
+                        Ok(_) => (),
+                        Err(e) => return Err(error::VMError::IO(e)),
+                    }
+                }
+            }
+            self.pc += 1;
+        }
+        self.output.flush().map_err(error::VMError::IO)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,158 @@
+//! Minimal `Read`/`Write` abstraction so the interpreter core can run
+//! without `std` (bare-metal, WASM, ...). When the `std` feature is on
+//! these are simply re-exports of `std::io::{Read, Write, BufReader,
+//! BufWriter}`; otherwise small traits/adapters are defined here and
+//! embedders implement the traits themselves.
+
+#[cfg(feature = "std")]
+pub use std::io::{BufReader, BufWriter, Read, Write};
+
+#[cfg(not(feature = "std"))]
+use alloc::{vec, vec::Vec};
+
+#[cfg(not(feature = "std"))]
+pub trait Read {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, IoError>;
+}
+
+#[cfg(not(feature = "std"))]
+pub trait Write {
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), IoError>;
+
+    /// Push any buffered data out to the underlying sink. The default
+    /// no-op is correct for a `Write` that doesn't buffer.
+    fn flush(&mut self) -> Result<(), IoError> {
+        Ok(())
+    }
+}
+
+/// The error type produced by a `Read`/`Write` implementation.
+#[cfg(feature = "std")]
+pub type IoError = std::io::Error;
+
+#[cfg(not(feature = "std"))]
+#[derive(Debug)]
+pub struct IoError;
+
+#[cfg(not(feature = "std"))]
+impl core::fmt::Display for IoError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "I/O error")
+    }
+}
+
+// `std::io::{Read, Write}` come with blanket impls for `Box<dyn Read/Write>`
+// (so a boxed trait object can itself be used as a generic `R: Read`/`W:
+// Write`, e.g. to build a `BufReader`/`BufWriter` around one); mirror that
+// here since our own traits don't get it for free.
+#[cfg(not(feature = "std"))]
+impl<R: Read + ?Sized> Read for alloc::boxed::Box<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, IoError> {
+        (**self).read(buf)
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl<W: Write + ?Sized> Write for alloc::boxed::Box<W> {
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), IoError> {
+        (**self).write_all(buf)
+    }
+
+    fn flush(&mut self) -> Result<(), IoError> {
+        (**self).flush()
+    }
+}
+
+/// Capacity of the hand-rolled `BufReader`/`BufWriter` below. `std`'s own
+/// types pick their own default (currently 8 KiB too, as it happens).
+#[cfg(not(feature = "std"))]
+const BUF_CAPACITY: usize = 8 * 1024;
+
+/// A minimal read buffer, so repeated single-byte `,` reads don't turn into
+/// one `Read::read` call apiece.
+#[cfg(not(feature = "std"))]
+pub struct BufReader<R> {
+    inner: R,
+    buf: Vec<u8>,
+    pos: usize,
+    filled: usize,
+}
+
+#[cfg(not(feature = "std"))]
+impl<R: Read> BufReader<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            buf: vec![0; BUF_CAPACITY],
+            pos: 0,
+            filled: 0,
+        }
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl<R: Read> Read for BufReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, IoError> {
+        if self.pos >= self.filled {
+            self.filled = self.inner.read(&mut self.buf)?;
+            self.pos = 0;
+            if self.filled == 0 {
+                return Ok(0);
+            }
+        }
+        let n = buf.len().min(self.filled - self.pos);
+        buf[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// A minimal write buffer, so a run of `.` writes reaches the underlying
+/// sink as one large slice instead of one byte at a time. Flushed when the
+/// buffer fills, and on drop (best-effort; callers that care about flush
+/// errors should call [`Write::flush`] explicitly before dropping).
+#[cfg(not(feature = "std"))]
+pub struct BufWriter<W: Write> {
+    inner: W,
+    buf: Vec<u8>,
+}
+
+#[cfg(not(feature = "std"))]
+impl<W: Write> BufWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            buf: Vec::with_capacity(BUF_CAPACITY),
+        }
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl<W: Write> Write for BufWriter<W> {
+    fn write_all(&mut self, buf: &[u8]) -> Result<(), IoError> {
+        if buf.len() >= BUF_CAPACITY {
+            self.flush()?;
+            return self.inner.write_all(buf);
+        }
+        if self.buf.len() + buf.len() > BUF_CAPACITY {
+            self.flush()?;
+        }
+        self.buf.extend_from_slice(buf);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), IoError> {
+        if !self.buf.is_empty() {
+            self.inner.write_all(&self.buf)?;
+            self.buf.clear();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "std"))]
+impl<W: Write> Drop for BufWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
@@ -1,4 +1,5 @@
-use std::{error::Error, fmt::Display};
+use alloc::{collections::BTreeMap, vec, vec::Vec};
+use core::fmt::Display;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BfIR {
@@ -10,6 +11,15 @@ pub enum BfIR {
     PutByte,     // .
     Jz(u32),     // [
     Jnz(u32),    // ]
+    // *ptr = v, generated from simple clear loops such as `[-]`/`[+]`
+    SetVal(u8),
+    // mem[ptr + offset] = mem[ptr + offset].wrapping_add(mem[ptr] * factor),
+    // generated from simple copy/multiply loops such as `[->+<]`
+    AddMul { offset: i32, factor: u8 },
+    // Write *ptr to output `count` times, generated by folding a run of
+    // consecutive `.`s (the cell is never touched in between, so every
+    // write is the same byte)
+    PutRepeat { count: u32 },
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -19,7 +29,7 @@ pub enum CompileErrorKind {
 }
 
 impl Display for CompileErrorKind {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         match self {
             CompileErrorKind::UncloseLeftBracket => write!(f, "Unclosed left bracket"),
             CompileErrorKind::UnexpectedRightBracket => write!(f, "Unclosed left bracket"),
@@ -27,7 +37,8 @@ impl Display for CompileErrorKind {
     }
 }
 
-impl Error for CompileErrorKind {}
+#[cfg(feature = "std")]
+impl std::error::Error for CompileErrorKind {}
 
 #[derive(Debug)]
 pub struct CompileError {
@@ -37,7 +48,7 @@ pub struct CompileError {
 }
 
 impl Display for CompileError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{} at line {}:{}", self.kind, self.line, self.col)
     }
 }
@@ -128,7 +139,27 @@ pub fn optimize_ir(code: &mut Vec<BfIR>) {
             BfIR::AddPtr(mut x) => fold_ir!(AddPtr, x),
             BfIR::SubPtr(mut x) => fold_ir!(SubPtr, x),
             BfIR::GetByte => normal_ir!(),
-            BfIR::PutByte => normal_ir!(),
+            BfIR::PutByte => {
+                // A run of consecutive `.`s writes the same unchanged cell
+                // each time, so fold it into one `PutRepeat` instead of N
+                // separate `PutByte`s.
+                let mut count = 1u32;
+                let mut j = i + 1;
+                while j < len && matches!(code[j], BfIR::PutByte) {
+                    count += 1;
+                    j += 1;
+                }
+                code[pc] = if count > 1 {
+                    BfIR::PutRepeat { count }
+                } else {
+                    BfIR::PutByte
+                };
+                i = j;
+                pc += 1;
+            }
+            BfIR::PutRepeat { .. }
+            | BfIR::SetVal(_)
+            | BfIR::AddMul { .. } => unreachable!("not produced before this pass"),
             BfIR::Jz(_) => {
                 code[pc] = BfIR::Jz(0);
                 stk.push(pc);
@@ -146,6 +177,114 @@ pub fn optimize_ir(code: &mut Vec<BfIR>) {
     }
     code.truncate(pc);
     code.shrink_to_fit();
+
+    optimize_simple_loops(code);
+}
+
+// A loop body made up only of `AddVal`/`SubVal`/`AddPtr`/`SubPtr` whose net
+// pointer movement is zero and whose net change on the loop cell (offset 0)
+// is exactly -1 or +1. Such a loop runs exactly `mem[ptr]` times (or its
+// 256's complement) and can be lowered to a handful of straight-line ops.
+struct SimpleLoop {
+    // (offset, factor) pairs to apply as `AddMul`, in ascending offset order
+    copies: Vec<(i32, u8)>,
+}
+
+fn analyze_simple_loop(body: &[BfIR]) -> Option<SimpleLoop> {
+    let mut ptr: i32 = 0;
+    let mut deltas: BTreeMap<i32, u8> = BTreeMap::new();
+
+    for ir in body {
+        match *ir {
+            BfIR::AddVal(x) => {
+                let d = deltas.entry(ptr).or_insert(0);
+                *d = d.wrapping_add(x);
+            }
+            BfIR::SubVal(x) => {
+                let d = deltas.entry(ptr).or_insert(0);
+                *d = d.wrapping_sub(x);
+            }
+            BfIR::AddPtr(x) => ptr = ptr.checked_add(x as i32)?,
+            BfIR::SubPtr(x) => ptr = ptr.checked_sub(x as i32)?,
+            // I/O and nested loops make the loop's effect data-dependent
+            BfIR::GetByte
+            | BfIR::PutByte
+            | BfIR::PutRepeat { .. }
+            | BfIR::Jz(_)
+            | BfIR::Jnz(_) => return None,
+            BfIR::SetVal(_) | BfIR::AddMul { .. } => return None,
+        }
+    }
+
+    if ptr != 0 {
+        return None;
+    }
+
+    let delta0 = *deltas.get(&0).unwrap_or(&0);
+    // modular inverse needed for any other delta; leave the loop untouched
+    if delta0 != 1 && delta0 != 0xffu8 {
+        return None;
+    }
+
+    let copies = deltas
+        .into_iter()
+        .filter(|&(offset, d)| offset != 0 && d != 0)
+        .map(|(offset, d)| {
+            // delta0 == -1: loop runs mem[ptr] times, so a straight multiply
+            // delta0 == +1: loop runs -mem[ptr] times (mod 256), so negate
+            let factor = if delta0 == 1 { d.wrapping_neg() } else { d };
+            (offset, factor)
+        })
+        .collect();
+
+    Some(SimpleLoop { copies })
+}
+
+// Recognize `[-]`/`[+]` (cell clear) and `[->+<]`-style (copy/multiply)
+// loops and lower them to `AddMul`/`SetVal`, which run in constant time
+// instead of looping `mem[ptr]` times.
+fn optimize_simple_loops(code: &mut Vec<BfIR>) {
+    let len = code.len();
+    let mut out = Vec::with_capacity(len);
+    let mut i = 0;
+
+    while i < len {
+        if let BfIR::Jz(jnz_pos) = code[i] {
+            let jnz_pos = jnz_pos as usize;
+            let body = &code[i + 1..jnz_pos];
+            if let Some(simple) = analyze_simple_loop(body) {
+                for (offset, factor) in simple.copies {
+                    out.push(BfIR::AddMul { offset, factor });
+                }
+                out.push(BfIR::SetVal(0));
+                i = jnz_pos + 1;
+                continue;
+            }
+        }
+        out.push(code[i]);
+        i += 1;
+    }
+
+    fixup_jumps(&mut out);
+    *code = out;
+}
+
+// Recompute `Jz`/`Jnz` targets after instructions have been removed or
+// reordered; targets point at each other's index, same convention as
+// `compile`/`optimize_ir`.
+fn fixup_jumps(code: &mut [BfIR]) {
+    let mut stk = vec![];
+    for pc in 0..code.len() {
+        match code[pc] {
+            BfIR::Jz(_) => stk.push(pc),
+            BfIR::Jnz(_) => {
+                let jz_pos = stk.pop().expect("unbalanced brackets");
+                code[pc] = BfIR::Jnz(jz_pos as u32);
+                code[jz_pos] = BfIR::Jz(pc as u32);
+            }
+            _ => {}
+        }
+    }
 }
 
 #[cfg(test)]
@@ -180,8 +319,79 @@ mod test {
 
     #[test]
     fn test_optimize() {
+        // delta on the loop cell is +7, not +-1, so this loop needs a
+        // modular inverse and is left as a real loop
         let mut code = compile("[+++++++]").unwrap();
         optimize_ir(&mut code);
         assert_eq!(code, vec![BfIR::Jz(2), BfIR::AddVal(7), BfIR::Jnz(0)]);
     }
+
+    #[test]
+    fn test_optimize_clear_loop() {
+        let mut code = compile("[-]").unwrap();
+        optimize_ir(&mut code);
+        assert_eq!(code, vec![BfIR::SetVal(0)]);
+    }
+
+    #[test]
+    fn test_optimize_copy_loop() {
+        let mut code = compile("[->+<]").unwrap();
+        optimize_ir(&mut code);
+        assert_eq!(
+            code,
+            vec![BfIR::AddMul { offset: 1, factor: 1 }, BfIR::SetVal(0)]
+        );
+    }
+
+    #[test]
+    fn test_optimize_multiply_loop() {
+        let mut code = compile("[->++>+++<<]").unwrap();
+        optimize_ir(&mut code);
+        assert_eq!(
+            code,
+            vec![
+                BfIR::AddMul { offset: 1, factor: 2 },
+                BfIR::AddMul { offset: 2, factor: 3 },
+                BfIR::SetVal(0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_optimize_fold_put_repeat() {
+        let mut code = compile("...").unwrap();
+        optimize_ir(&mut code);
+        assert_eq!(code, vec![BfIR::PutRepeat { count: 3 }]);
+    }
+
+    #[test]
+    fn test_optimize_put_repeat_breaks_on_cell_change() {
+        let mut code = compile("..+.").unwrap();
+        optimize_ir(&mut code);
+        assert_eq!(
+            code,
+            vec![
+                BfIR::PutRepeat { count: 2 },
+                BfIR::AddVal(1),
+                BfIR::PutByte,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_optimize_leaves_non_simple_loop() {
+        // net pointer movement is non-zero, so this is left as a real loop
+        let mut code = compile("[->+]").unwrap();
+        optimize_ir(&mut code);
+        assert_eq!(
+            code,
+            vec![
+                BfIR::Jz(4),
+                BfIR::SubVal(1),
+                BfIR::AddPtr(1),
+                BfIR::AddVal(1),
+                BfIR::Jnz(0),
+            ]
+        );
+    }
 }
@@ -1,204 +1,657 @@
-use std::{
-    io::{Read, Write},
-    path::Path,
-    ptr,
-};
-
-use dynasmrt::{dynasm, DynasmApi, DynasmLabelApi};
-
-use crate::{
-    error::{Result, RuntimeError, VMError},
-    ir::{self, BfIR},
-};
-
-const MEM_SIZE: usize = 4 * 1024 * 1024;
-
-pub struct VM {
-    code: dynasmrt::ExecutableBuffer,
-    start: dynasmrt::AssemblyOffset,
-    memory: Box<[u8]>,
-    input: Box<dyn Read>,
-    output: Box<dyn Write>,
-}
-
-fn vm_error(re: RuntimeError) -> *mut VMError {
-    let e = Box::new(VMError::from(re));
-    Box::into_raw(e)
-}
-
-impl VM {
-    unsafe extern "sysv64" fn getbyte(this: *mut Self, ptr: *mut u8) -> *mut VMError {
-        let mut buf = [0_u8];
-        let this = &mut *this;
-        match this.input.read(&mut buf) {
-            Ok(0) => {}
-            Ok(1) => *ptr = buf[0],
-            Err(e) => return vm_error(RuntimeError::IO(e)),
-            _ => unreachable!(),
-        }
-        ptr::null_mut()
-    }
-
-    unsafe extern "sysv64" fn putbyte(this: *mut Self, ptr: *const u8) -> *mut VMError {
-        let buf = std::slice::from_ref(&*ptr);
-        let this = &mut *this;
-        match this.output.write_all(buf) {
-            Ok(()) => ptr::null_mut(),
-            Err(e) => vm_error(RuntimeError::IO(e)),
-        }
-    }
-
-    unsafe extern "sysv64" fn overflow_error() -> *mut VMError {
-        vm_error(RuntimeError::PointerOverflow)
-    }
-}
-
-impl VM {
-    pub fn new<P: AsRef<Path>>(
-        file_path: P,
-        input: Box<dyn Read>,
-        output: Box<dyn Write>,
-        optimize: bool,
-    ) -> Result<Self> {
-        let src = std::fs::read_to_string(file_path)?;
-        let mut ir = ir::compile(&src)?;
-        drop(src);
-
-        if optimize {
-            ir::optimize_ir(&mut ir);
-        }
-        let (code, start) = Self::compile(&ir)?;
-        drop(ir);
-
-        let memory = vec![0; MEM_SIZE].into_boxed_slice();
-        Ok(Self {
-            code,
-            start,
-            memory,
-            input,
-            output,
-        })
-    }
-
-    pub fn run(&mut self) -> Result<()> {
-        type RawFn = unsafe extern "sysv64" fn(
-            this: *mut VM,
-            memory_start: *const u8,
-            memory_end: *const u8,
-        ) -> *mut VMError;
-
-        let raw_fn = unsafe { std::mem::transmute::<_, RawFn>(self.code.ptr(self.start)) };
-
-        let this: *mut Self = self;
-        let memory_start = self.memory.as_mut_ptr();
-        let memory_end = unsafe { memory_start.add(MEM_SIZE) };
-
-        let ret = unsafe { raw_fn(this, memory_start, memory_end) };
-
-        if ret.is_null() {
-            Ok(())
-        } else {
-            Err(*unsafe { Box::from_raw(ret) })
-        }
-    }
-
-    fn compile<IR: AsRef<[BfIR]>>(
-        code: IR,
-    ) -> Result<(dynasmrt::ExecutableBuffer, dynasmrt::AssemblyOffset)> {
-        let mut ops = dynasmrt::x64::Assembler::new()?;
-        let start = ops.offset();
-
-        let mut loops = vec![];
-
-        // this:         rdi r12
-        // memory_start: rsi r13
-        // memory_end:   rdx r14
-        // ptr:          rcx r15
-
-        dynasm!(ops
-            ; push rax
-            ; mov r12, rdi   // save this
-            ; mov r13, rsi   // save memory_start
-            ; mov r14, rdx   // save memory_end
-            ; mov rcx, rsi   // ptr = memory_start
-        );
-
-        for &ir in code.as_ref().iter() {
-            match ir {
-                BfIR::AddVal(x) => dynasm!(ops
-                    ; add BYTE [rcx], x as i8    // *ptr += x
-                ),
-                BfIR::SubVal(x) => dynasm!(ops
-                    ; sub BYTE [rcx], x as i8    // *ptr -= x
-                ),
-                BfIR::AddPtr(x) => dynasm!(ops
-                    ; add rcx, x as i32     // ptr += x
-                    ; jc  ->overflow        // jmp if overflow
-                    ; cmp rcx, r14          // ptr - memory_end
-                    ; jnb ->overflow        // jmp if ptr >= memory_end
-                ),
-                BfIR::SubPtr(x) => dynasm!(ops
-                    ; sub rcx, x as i32     // ptr -= x
-                    ; jc  ->overflow        // jmp if overflow
-                    ; cmp rcx, r13          // ptr - memory_start
-                    ; jb  ->overflow        // jmp if ptr < memory_start
-                ),
-                BfIR::GetByte => dynasm!(ops
-                    ; mov  r15, rcx         // save ptr
-                    ; mov  rdi, r12
-                    ; mov  rsi, rcx         // arg0: this, arg1: ptr
-                    ; mov  rax, QWORD VM::getbyte as _
-                    ; call rax              // getbyte(this, ptr)
-                    ; test rax, rax
-                    ; jnz  ->io_error       // jmp if rax != 0
-                    ; mov  rcx, r15         // recover ptr
-                ),
-                BfIR::PutByte => dynasm!(ops
-                    ; mov  r15, rcx         // save ptr
-                    ; mov  rdi, r12
-                    ; mov  rsi, rcx         // arg0: this, arg1: ptr
-                    ; mov  rax, QWORD VM::putbyte as _
-                    ; call rax              // putbyte(this, ptr)
-                    ; test rax, rax
-                    ; jnz  ->io_error       // jmp if rax != 0
-                    ; mov  rcx, r15         // recover ptr
-                ),
-                BfIR::Jz => {
-                    let left = ops.new_dynamic_label();
-                    let right = ops.new_dynamic_label();
-                    loops.push((left, right));
-
-                    dynasm!(ops
-                        ; cmp BYTE [rcx], 0
-                        ; jz => right       // jmp if *ptr == 0
-                        ; => left
-                    )
-                }
-                BfIR::Jnz => {
-                    let (left, right) = loops.pop().unwrap();
-                    dynasm!(ops
-                        ; cmp BYTE [rcx], 0
-                        ; jnz => left       // jmp if *ptr != 0
-                        ; => right
-                    )
-                }
-            }
-        }
-
-        dynasm!(ops
-            ; xor rax, rax
-            ; jmp >exit
-            ; -> overflow:
-            ; mov rax, QWORD VM::overflow_error as _
-            ; call rax
-            ; jmp >exit
-            ; -> io_error:
-            ; exit:
-            ; pop rdx
-            ; ret
-        );
-
-        let code = ops.finalize().unwrap();
-        Ok((code, start))
-    }
-}
+use std::{
+    io::{Read, Write},
+    path::Path,
+    ptr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use dynasmrt::{dynasm, DynasmApi, DynasmLabelApi};
+
+use crate::{
+    error::{Result, RuntimeError, VMError},
+    io::{BufReader, BufWriter},
+    ir::{self, BfIR},
+    tape::{DEFAULT_MAX_PAGES, DEFAULT_PAGE_SIZE},
+};
+
+const MEM_SIZE: usize = 4 * 1024 * 1024;
+
+/// Generated machine code, the offset of its entry point, and (when
+/// `track_boundaries` is set) the IR op each instruction range came from,
+/// for `--emit asm` to annotate its disassembly with.
+type CompileResult = Result<(
+    dynasmrt::ExecutableBuffer,
+    dynasmrt::AssemblyOffset,
+    alloc::vec::Vec<(usize, BfIR)>,
+)>;
+
+/// How the JIT's tape is sized. `Fixed` is the original flat buffer;
+/// `Paged` grows in `page_size` increments (up to `max_pages` of them) as
+/// the program's pointer wanders outside the currently mapped range, so it
+/// can't hit `PointerOverflow` just by going far enough left or right.
+///
+/// Unlike [`crate::tape::PagedTape`] (a sparse page map used by the
+/// interpreter), the JIT needs a single contiguous, absolutely-addressed
+/// buffer so `[rcx]`-style addressing keeps working, so "paging" here means
+/// growing one contiguous allocation by a page at a time rather than
+/// mapping in disjoint pages.
+#[derive(Debug, Clone, Copy)]
+pub enum TapeMode {
+    Fixed,
+    Paged {
+        page_size: usize,
+        max_pages: usize,
+    },
+}
+
+impl Default for TapeMode {
+    fn default() -> Self {
+        TapeMode::Paged {
+            page_size: DEFAULT_PAGE_SIZE,
+            max_pages: DEFAULT_MAX_PAGES,
+        }
+    }
+}
+
+pub struct VM {
+    code: dynasmrt::ExecutableBuffer,
+    start: dynasmrt::AssemblyOffset,
+    // The tape's backing allocation. For `TapeMode::Paged`, grown (and
+    // relocated) a page at a time by `grow` as the pointer wanders outside
+    // the current range.
+    memory: Vec<u8>,
+    // Index within `memory` that corresponds to logical tape position 0.
+    origin: usize,
+    tape_mode: TapeMode,
+    input: Box<dyn Read>,
+    output: Box<dyn Write>,
+    // Loop back-edge budget handed to the generated code on every `run`;
+    // kept at `u64::MAX` when the caller didn't ask for a limit, so the
+    // decrement-and-check at each `Jnz` back-edge is unconditional and
+    // cheap rather than gated behind a runtime branch. Unlike
+    // `Interpreter`, which spends one unit of fuel per executed IR op, the
+    // JIT only spends it at loop back-edges (so a loop-free program never
+    // touches it at all) -- `--max-steps N` therefore means something
+    // different between the two engines; see `Opt::max_steps`'s help text.
+    max_steps: u64,
+    // Checked at the same back-edges as `max_steps`. Set from a timer
+    // thread spawned in `run` when a timeout was configured; `Arc`'d so the
+    // allocation outlives `VM` if the timer thread is still sleeping when
+    // `run` returns, since the generated code only holds a raw pointer into it.
+    timed_out: Arc<AtomicBool>,
+    timeout: Option<Duration>,
+    // Set by a trampoline right before it returns `error_sentinel()`, and
+    // taken back out by `run` once the generated code returns. Lets the
+    // `sysv64` callbacks signal an error without allocating (see
+    // `VM::fail`), which matters since the bounds-check trampolines run on
+    // every `AddPtr`/`SubPtr`.
+    pending_error: Option<VMError>,
+}
+
+/// Any nonzero value works here: the generated code only ever does `test
+/// rax, rax; jnz ...` on a trampoline's return value, it never dereferences
+/// it. The real error lives in `VM::pending_error`.
+fn error_sentinel() -> *mut VMError {
+    ptr::dangling_mut::<VMError>()
+}
+
+impl VM {
+    fn fail(&mut self, error: impl Into<VMError>) -> *mut VMError {
+        self.pending_error = Some(error.into());
+        error_sentinel()
+    }
+
+    unsafe extern "sysv64" fn getbyte(this: *mut Self, ptr: *mut u8) -> *mut VMError {
+        let this = &mut *this;
+        // Flush any buffered output before blocking on input, so a prompt
+        // the program just wrote is actually visible.
+        if let Err(e) = this.output.flush() {
+            return this.fail(RuntimeError::IO(e));
+        }
+        let mut buf = [0_u8];
+        match this.input.read(&mut buf) {
+            Ok(0) => {}
+            Ok(1) => *ptr = buf[0],
+            Err(e) => return this.fail(RuntimeError::IO(e)),
+            _ => unreachable!(),
+        }
+        ptr::null_mut()
+    }
+
+    unsafe extern "sysv64" fn putbyte(this: *mut Self, ptr: *const u8) -> *mut VMError {
+        let buf = std::slice::from_ref(&*ptr);
+        let this = &mut *this;
+        match this.output.write_all(buf) {
+            Ok(()) => ptr::null_mut(),
+            Err(e) => this.fail(RuntimeError::IO(e)),
+        }
+    }
+
+    /// Write `*ptr` to output `count` times; generated for `BfIR::PutRepeat`
+    /// so a run of `.`s crosses the JIT/Rust boundary once instead of once
+    /// per byte. Each write still only touches `output`'s internal buffer
+    /// (see [`crate::io::BufWriter`]), which is what actually coalesces the
+    /// underlying `write`s.
+    unsafe extern "sysv64" fn putrepeat(
+        this: *mut Self,
+        ptr: *const u8,
+        count: u64,
+    ) -> *mut VMError {
+        let buf = std::slice::from_ref(&*ptr);
+        let this = &mut *this;
+        for _ in 0..count {
+            if let Err(e) = this.output.write_all(buf) {
+                return this.fail(RuntimeError::IO(e));
+            }
+        }
+        ptr::null_mut()
+    }
+
+    unsafe extern "sysv64" fn overflow_error(this: *mut Self) -> *mut VMError {
+        (&mut *this).fail(RuntimeError::PointerOverflow)
+    }
+
+    unsafe extern "sysv64" fn fuel_exhausted_error(this: *mut Self) -> *mut VMError {
+        (&mut *this).fail(RuntimeError::FuelExhausted)
+    }
+
+    unsafe extern "sysv64" fn timed_out_error(this: *mut Self) -> *mut VMError {
+        (&mut *this).fail(RuntimeError::TimedOut)
+    }
+
+    /// Out-of-range pointer trampoline for `TapeMode::Paged`: grows the
+    /// tape a page at a time in the direction the pointer overflowed,
+    /// repeating until the pointer actually lands back in range (a single
+    /// `AddPtr`/`SubPtr`/`AddMul` can jump by more than one page at once,
+    /// e.g. a long `>`/`<` run folded by the optimizer), and writes the
+    /// updated pointer/bounds back through the out-params so the generated
+    /// code can reload `rcx`/`r13`/`r14` and carry on. On a fixed tape (or
+    /// once `max_pages` is reached) this is a hard error, same as the
+    /// non-paged bounds check.
+    unsafe extern "sysv64" fn grow(
+        this: *mut Self,
+        ptr: *mut u8,
+        direction: i64,
+        out_ptr: *mut *mut u8,
+        out_start: *mut *const u8,
+        out_end: *mut *const u8,
+    ) -> *mut VMError {
+        let this = &mut *this;
+        match this.grow_memory(ptr, direction) {
+            Ok((new_ptr, new_start, new_end)) => {
+                *out_ptr = new_ptr;
+                *out_start = new_start;
+                *out_end = new_end;
+                ptr::null_mut()
+            }
+            Err(e) => this.fail(e),
+        }
+    }
+
+    fn grow_memory(
+        &mut self,
+        ptr: *mut u8,
+        direction: i64,
+    ) -> std::result::Result<(*mut u8, *const u8, *const u8), RuntimeError> {
+        let (page_size, max_pages) = match self.tape_mode {
+            TapeMode::Fixed => return Err(RuntimeError::PointerOverflow),
+            TapeMode::Paged {
+                page_size,
+                max_pages,
+            } => (page_size, max_pages),
+        };
+
+        // `ptr` only has to be re-expressed in terms of each new allocation
+        // as we go (via `logical_offset`, computed fresh every iteration);
+        // it keeps pointing into whichever `memory` was current when it was
+        // last assigned below.
+        let mut ptr = ptr;
+        loop {
+            if self.memory.len() + page_size > page_size * max_pages {
+                return Err(RuntimeError::PointerOverflow);
+            }
+
+            // Offset of the faulting pointer relative to logical position
+            // 0, computed before growing (and copying) `memory`.
+            let old_base = self.memory.as_ptr() as isize;
+            let logical_offset = ptr as isize - (old_base + self.origin as isize);
+
+            let mut new_memory = vec![0_u8; self.memory.len() + page_size];
+            let new_origin = if direction < 0 {
+                self.origin + page_size
+            } else {
+                self.origin
+            };
+            let copy_at = new_origin - self.origin;
+            new_memory[copy_at..copy_at + self.memory.len()].copy_from_slice(&self.memory);
+
+            self.memory = new_memory;
+            self.origin = new_origin;
+
+            let new_base = self.memory.as_mut_ptr();
+            let new_ptr = unsafe { new_base.offset(self.origin as isize + logical_offset) };
+            let new_start = new_base as *const u8;
+            let new_end = unsafe { new_base.add(self.memory.len()) as *const u8 };
+
+            if new_ptr >= new_base && (new_ptr as *const u8) < new_end {
+                return Ok((new_ptr, new_start, new_end));
+            }
+            // Still out of range (the jump was bigger than one page): grow
+            // another page in the same direction and re-test.
+            ptr = new_ptr;
+        }
+    }
+}
+
+impl VM {
+    pub fn new<P: AsRef<Path>>(
+        file_path: P,
+        input: Box<dyn Read>,
+        output: Box<dyn Write>,
+        optimize: bool,
+        max_steps: Option<u64>,
+        timeout: Option<Duration>,
+        tape_mode: TapeMode,
+    ) -> Result<Self> {
+        let src = std::fs::read_to_string(file_path)?;
+        let mut ir = ir::compile(&src)?;
+        drop(src);
+
+        if optimize {
+            ir::optimize_ir(&mut ir);
+        }
+        let paged = matches!(tape_mode, TapeMode::Paged { .. });
+        let (code, start) = Self::compile(&ir, paged)?;
+        drop(ir);
+
+        let (memory, origin) = match tape_mode {
+            TapeMode::Fixed => (vec![0; MEM_SIZE], 0),
+            // Start with room on both sides of the origin so short programs
+            // never have to fault at all.
+            TapeMode::Paged { page_size, .. } => (vec![0; page_size * 2], page_size),
+        };
+        Ok(Self {
+            code,
+            start,
+            memory,
+            origin,
+            tape_mode,
+            input: Box::new(BufReader::new(input)),
+            output: Box::new(BufWriter::new(output)),
+            max_steps: max_steps.unwrap_or(u64::MAX),
+            timed_out: Arc::new(AtomicBool::new(false)),
+            timeout,
+            pending_error: None,
+        })
+    }
+
+    pub fn run(&mut self) -> Result<()> {
+        // The generated code only spends fuel at `Jnz` back-edges (via a
+        // `dec`-then-check-zero, so the common case of a nonzero budget
+        // stays a single cheap instruction), which means a starting value
+        // of 0 would underflow past `u64::MAX` on the first decrement
+        // instead of firing. Check the zero budget up front instead, same
+        // as the interpreter's `if *fuel == 0` check before it executes
+        // anything.
+        if self.max_steps == 0 {
+            return Err(RuntimeError::FuelExhausted.into());
+        }
+
+        type RawFn = unsafe extern "sysv64" fn(
+            this: *mut VM,
+            memory_start: *const u8,
+            memory_end: *const u8,
+            max_steps: u64,
+            timed_out: *const AtomicBool,
+            initial_ptr: *const u8,
+        ) -> *mut VMError;
+
+        let raw_fn =
+            unsafe { std::mem::transmute::<*const u8, RawFn>(self.code.ptr(self.start)) };
+
+        self.timed_out.store(false, Ordering::Relaxed);
+        let timed_out_ptr: *const AtomicBool = Arc::as_ptr(&self.timed_out);
+
+        // The thread holds its own `Arc` clone, so the flag stays alive for
+        // it to write to even if `run` returns (and `self` is dropped)
+        // before the timeout elapses.
+        if let Some(timeout) = self.timeout {
+            let timed_out = Arc::clone(&self.timed_out);
+            std::thread::spawn(move || {
+                std::thread::sleep(timeout);
+                timed_out.store(true, Ordering::Relaxed);
+            });
+        }
+
+        let this: *mut Self = self;
+        let memory_start = self.memory.as_mut_ptr();
+        let memory_end = unsafe { memory_start.add(self.memory.len()) };
+        let initial_ptr = unsafe { memory_start.add(self.origin) };
+        let max_steps = self.max_steps;
+
+        let ret = unsafe {
+            raw_fn(
+                this,
+                memory_start,
+                memory_end,
+                max_steps,
+                timed_out_ptr,
+                initial_ptr,
+            )
+        };
+
+        if ret.is_null() {
+            self.output.flush().map_err(RuntimeError::IO)?;
+            Ok(())
+        } else {
+            Err(self
+                .pending_error
+                .take()
+                .expect("trampoline returned an error sentinel without setting pending_error"))
+        }
+    }
+
+    fn compile<IR: AsRef<[BfIR]>>(
+        code: IR,
+        paged: bool,
+    ) -> Result<(dynasmrt::ExecutableBuffer, dynasmrt::AssemblyOffset)> {
+        let (code, start, _boundaries) = Self::compile_inner(code, false, paged)?;
+        Ok((code, start))
+    }
+
+    /// Like [`VM::compile`], but also records the byte offset at which each
+    /// source `BfIR` op starts emitting, so a disassembler can tag the
+    /// generated instructions with the op that produced them.
+    #[cfg(feature = "disasm")]
+    pub fn compile_annotated<IR: AsRef<[BfIR]>>(code: IR, paged: bool) -> CompileResult {
+        Self::compile_inner(code, true, paged)
+    }
+
+    fn compile_inner<IR: AsRef<[BfIR]>>(
+        code: IR,
+        track_boundaries: bool,
+        paged: bool,
+    ) -> CompileResult {
+        let mut ops = dynasmrt::x64::Assembler::new()?;
+        let start = ops.offset();
+
+        let mut loops = vec![];
+        let mut boundaries = alloc::vec::Vec::new();
+
+        // this:          rdi r12
+        // memory_start:  rsi r13
+        // memory_end:    rdx r14
+        // fuel:          rcx (arg) -> rbx
+        // timed_out*:    r8  (arg) -> rbp
+        // initial_ptr:   r9  (arg)
+        // ptr:           rcx r15
+        //
+        // fuel/timed_out live in rbx/rbp (callee-saved) rather than some
+        // other free-looking register because they have to survive the
+        // `call`s to the getbyte/putbyte/putrepeat/grow trampolines, which
+        // are ordinary SysV calls and so are free to clobber any
+        // caller-saved register (rax/rcx/rdx/rsi/rdi/r8-r11) across them.
+        // Since rbx/rbp are callee-saved by *this* function's own contract
+        // with its Rust caller, they're saved/restored around the whole
+        // body instead (see the matching `pop`s at `exit`).
+
+        dynasm!(ops
+            ; push rax   // stack alignment filler, discarded at `exit`
+            ; push rbx
+            ; push rbp
+            ; mov r12, rdi   // save this
+            ; mov r13, rsi   // save memory_start
+            ; mov r14, rdx   // save memory_end
+            ; mov rbx, rcx   // save fuel (4th arg, arrives in rcx)
+            ; mov rbp, r8    // save timed_out pointer (5th arg, arrives in r8)
+            ; mov rcx, r9    // ptr = initial_ptr (6th arg, arrives in r9)
+        );
+
+        for &ir in code.as_ref().iter() {
+            if track_boundaries {
+                boundaries.push((ops.offset().0, ir));
+            }
+            match ir {
+                BfIR::AddVal(x) => dynasm!(ops
+                    ; add BYTE [rcx], x as i8    // *ptr += x
+                ),
+                BfIR::SubVal(x) => dynasm!(ops
+                    ; sub BYTE [rcx], x as i8    // *ptr -= x
+                ),
+                BfIR::AddPtr(x) if !paged => dynasm!(ops
+                    ; add rcx, x as i32     // ptr += x
+                    ; jc  ->overflow        // jmp if overflow
+                    ; cmp rcx, r14          // ptr - memory_end
+                    ; jnb ->overflow        // jmp if ptr >= memory_end
+                ),
+                BfIR::SubPtr(x) if !paged => dynasm!(ops
+                    ; sub rcx, x as i32     // ptr -= x
+                    ; jc  ->overflow        // jmp if overflow
+                    ; cmp rcx, r13          // ptr - memory_start
+                    ; jb  ->overflow        // jmp if ptr < memory_start
+                ),
+                // Paged mode: the same bounds check, except running off the
+                // end of the mapped range calls `VM::grow` instead of
+                // failing outright, then reloads ptr/memory_start/memory_end
+                // from the out-params it writes.
+                BfIR::AddPtr(x) => {
+                    let cont = ops.new_dynamic_label();
+                    dynasm!(ops
+                        ; add rcx, x as i32     // ptr += x
+                        ; jc  ->overflow        // jmp if overflow
+                        ; cmp rcx, r14          // ptr - memory_end
+                        ; jb  => cont           // jmp if ptr < memory_end
+                        ; mov rdx, 1            // direction: growing past the end
+                        ; call ->grow
+                        ; => cont
+                    )
+                }
+                BfIR::SubPtr(x) => {
+                    let cont = ops.new_dynamic_label();
+                    dynasm!(ops
+                        ; sub rcx, x as i32     // ptr -= x
+                        ; jc  ->overflow        // jmp if overflow
+                        ; cmp rcx, r13          // ptr - memory_start
+                        ; jae => cont           // jmp if ptr >= memory_start
+                        ; mov rdx, -1           // direction: growing past the start
+                        ; call ->grow
+                        ; => cont
+                    )
+                }
+                BfIR::GetByte => dynasm!(ops
+                    ; mov  r15, rcx         // save ptr
+                    ; mov  rdi, r12
+                    ; mov  rsi, rcx         // arg0: this, arg1: ptr
+                    ; mov  rax, QWORD VM::getbyte as *const () as _
+                    ; call rax              // getbyte(this, ptr)
+                    ; test rax, rax
+                    ; jnz  ->propagate_error // jmp if rax != 0
+                    ; mov  rcx, r15         // recover ptr
+                ),
+                BfIR::PutByte => dynasm!(ops
+                    ; mov  r15, rcx         // save ptr
+                    ; mov  rdi, r12
+                    ; mov  rsi, rcx         // arg0: this, arg1: ptr
+                    ; mov  rax, QWORD VM::putbyte as *const () as _
+                    ; call rax              // putbyte(this, ptr)
+                    ; test rax, rax
+                    ; jnz  ->propagate_error // jmp if rax != 0
+                    ; mov  rcx, r15         // recover ptr
+                ),
+                // The JIT builds its own dynamic labels for loop bodies
+                // rather than using the jump targets `ir::compile` baked
+                // into `Jz`/`Jnz` (those index into the `Vec<BfIR>`, which
+                // has no meaning once lowered to machine code), so the
+                // target is ignored here.
+                BfIR::Jz(_) => {
+                    let left = ops.new_dynamic_label();
+                    let right = ops.new_dynamic_label();
+                    loops.push((left, right));
+
+                    dynasm!(ops
+                        ; cmp BYTE [rcx], 0
+                        ; jz => right       // jmp if *ptr == 0
+                        ; => left
+                    )
+                }
+                BfIR::Jnz(_) => {
+                    let (left, right) = loops.pop().unwrap();
+                    dynasm!(ops
+                        ; dec rbx
+                        ; jz  ->fuel_exhausted        // jmp if the step budget is spent
+                        ; cmp BYTE [rbp], 0
+                        ; jnz ->timed_out             // jmp if the wall-clock deadline passed
+                        ; cmp BYTE [rcx], 0
+                        ; jnz => left       // jmp if *ptr != 0
+                        ; => right
+                    )
+                }
+                BfIR::PutRepeat { count } => dynasm!(ops
+                    ; mov  r15, rcx         // save ptr
+                    ; mov  rdi, r12
+                    ; mov  rsi, rcx         // arg0: this, arg1: ptr
+                    ; mov  edx, count as i32 // arg2: count (zero-extends into rdx)
+                    ; mov  rax, QWORD VM::putrepeat as *const () as _
+                    ; call rax              // putrepeat(this, ptr, count)
+                    ; test rax, rax
+                    ; jnz  ->propagate_error // jmp if rax != 0
+                    ; mov  rcx, r15         // recover ptr
+                ),
+                BfIR::SetVal(v) => dynasm!(ops
+                    ; mov BYTE [rcx], v as i8   // *ptr = v
+                ),
+                // `offset` is a fixed compile-time displacement, so unlike
+                // `AddPtr`/`SubPtr` we statically know which single bound
+                // the target could ever cross: a positive offset can only
+                // run off the end, a negative one only off the start. Bail
+                // the same way `AddPtr`/`SubPtr` do if it would.
+                BfIR::AddMul { offset, factor } if !paged && offset >= 0 => dynasm!(ops
+                    ; lea rdx, [rcx + offset]   // rdx = ptr + offset (target address)
+                    ; movzx eax, BYTE [rcx]     // eax = *ptr
+                    ; imul eax, eax, factor as i32 // eax *= factor
+                    ; cmp rdx, r14              // target - memory_end
+                    ; jae ->overflow            // jmp if target >= memory_end
+                    ; add BYTE [rdx], al        // *(ptr + offset) += eax as u8
+                ),
+                BfIR::AddMul { offset, factor } if !paged => dynasm!(ops
+                    ; lea rdx, [rcx + offset]   // rdx = ptr + offset (target address), offset < 0
+                    ; movzx eax, BYTE [rcx]     // eax = *ptr
+                    ; imul eax, eax, factor as i32 // eax *= factor
+                    ; cmp rdx, r13              // target - memory_start
+                    ; jb  ->overflow            // jmp if target < memory_start
+                    ; add BYTE [rdx], al        // *(ptr + offset) += eax as u8
+                ),
+                // Paged mode: same idea, except running off the mapped
+                // range calls `VM::grow` (via the shared `->grow`
+                // trampoline) instead of failing outright. `->grow` expects
+                // the faulting pointer in rcx, calls into `VM::grow` (which
+                // clobbers rdi/rsi/rcx/r8/r9/rax), and leaves the reloaded
+                // ptr/memory_start/memory_end in rcx/r13/r14 on return, so
+                // the byte to add is stashed in r15d (untouched by `->grow`)
+                // across the call, and `rcx` gets shifted back from the
+                // target to the original ptr afterwards.
+                BfIR::AddMul { offset, factor } if offset >= 0 => {
+                    let cont = ops.new_dynamic_label();
+                    dynasm!(ops
+                        ; lea rdx, [rcx + offset]   // rdx = ptr + offset (target address)
+                        ; movzx eax, BYTE [rcx]     // eax = *ptr
+                        ; imul eax, eax, factor as i32 // eax *= factor
+                        ; cmp rdx, r14              // target - memory_end
+                        ; jb  >in_range             // jmp if target < memory_end
+                        ; mov r15d, eax             // stash the byte to add across ->grow
+                        ; mov rcx, rdx              // ptr arg for ->grow: the faulting target
+                        ; mov rdx, 1                // direction: growing past the end
+                        ; call ->grow               // reloads rcx(=new target)/r13/r14
+                        ; mov eax, r15d             // recover the byte to add
+                        ; add BYTE [rcx], al        // *(new target) += eax as u8
+                        ; sub rcx, offset           // rcx = shifted ptr (target - offset)
+                        ; jmp => cont
+                        ; in_range:
+                        ; add BYTE [rdx], al        // *(ptr + offset) += eax as u8
+                        ; => cont
+                    )
+                }
+                BfIR::AddMul { offset, factor } => {
+                    let cont = ops.new_dynamic_label();
+                    dynasm!(ops
+                        ; lea rdx, [rcx + offset]   // rdx = ptr + offset (target address), offset < 0
+                        ; movzx eax, BYTE [rcx]     // eax = *ptr
+                        ; imul eax, eax, factor as i32 // eax *= factor
+                        ; cmp rdx, r13              // target - memory_start
+                        ; jae >in_range             // jmp if target >= memory_start
+                        ; mov r15d, eax             // stash the byte to add across ->grow
+                        ; mov rcx, rdx              // ptr arg for ->grow: the faulting target
+                        ; mov rdx, -1               // direction: growing past the start
+                        ; call ->grow               // reloads rcx(=new target)/r13/r14
+                        ; mov eax, r15d             // recover the byte to add
+                        ; add BYTE [rcx], al        // *(new target) += eax as u8
+                        ; sub rcx, offset           // rcx = shifted ptr (target - offset)
+                        ; jmp => cont
+                        ; in_range:
+                        ; add BYTE [rdx], al        // *(ptr + offset) += eax as u8
+                        ; => cont
+                    )
+                }
+            }
+        }
+
+        dynasm!(ops
+            ; xor rax, rax
+            ; jmp >exit
+            ; -> overflow:
+            ; mov rdi, r12   // arg0: this
+            ; mov rax, QWORD VM::overflow_error as *const () as _
+            ; call rax
+            ; jmp >exit
+            ; -> fuel_exhausted:
+            ; mov rdi, r12   // arg0: this
+            ; mov rax, QWORD VM::fuel_exhausted_error as *const () as _
+            ; call rax
+            ; jmp >exit
+            ; -> timed_out:
+            ; mov rdi, r12   // arg0: this
+            ; mov rax, QWORD VM::timed_out_error as *const () as _
+            ; call rax
+            ; jmp >exit
+            // Out-of-range AddPtr/SubPtr in paged mode `call` here (not
+            // `jmp`) with ptr in rcx and direction in rdx, and it `ret`s
+            // back to the call site on success, having reloaded
+            // rcx/r13/r14 from `VM::grow`'s out-params. `sub rsp, 24`
+            // holds exactly the 3 out-params and keeps the stack
+            // 16-byte-aligned for the nested call, since we're entered via
+            // `call` ourselves (rsp % 16 == 8 here, same as any call site).
+            ; -> grow:
+            ; sub rsp, BYTE 24
+            ; mov rdi, r12      // arg0: this
+            ; mov rsi, rcx      // arg1: ptr
+            ; lea rcx, [rsp]        // arg3: out_ptr
+            ; lea r8, [rsp + 8]     // arg4: out_start
+            ; lea r9, [rsp + 16]    // arg5: out_end
+            ; mov rax, QWORD VM::grow as *const () as _
+            ; call rax              // grow(this, ptr, direction, out_ptr, out_start, out_end)
+            ; test rax, rax
+            ; jnz >grow_failed
+            ; mov rcx, [rsp]
+            ; mov r13, [rsp + 8]
+            ; mov r14, [rsp + 16]
+            ; add rsp, BYTE 24
+            ; ret
+            ; grow_failed:
+            ; add rsp, BYTE 32  // drop the out-params and our own return address
+            ; jmp ->propagate_error
+            ; -> propagate_error:
+            ; exit:
+            ; pop rbp
+            ; pop rbx
+            ; pop rdx   // discard the alignment filler pushed as `rax`
+            ; ret
+        );
+
+        let code = ops.finalize().unwrap();
+        Ok((code, start, boundaries))
+    }
+}
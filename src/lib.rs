@@ -0,0 +1,21 @@
+//! Core Brainfuck compile/optimize/execute pipeline.
+//!
+//! This crate is `no_std` when built without its default `std` feature (it
+//! still needs `alloc` for the IR buffer and the tape) so it can be embedded
+//! in bare-metal or WASM hosts.
+//! The `std` feature brings in `std::io`-backed `Read`/`Write` and the
+//! `jit` backend, which needs an OS to mmap executable memory. `main.rs`
+//! is a thin `std`-only CLI front end built on top of this library.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "disasm")]
+pub mod disasm;
+pub mod error;
+pub mod inter;
+pub mod io;
+pub mod ir;
+#[cfg(feature = "std")]
+pub mod jit;
+pub mod tape;
@@ -1,16 +1,36 @@
-mod error;
-mod inter;
-mod ir;
-mod jit;
-
 use std::{
     io::{stdin, stdout},
     path::PathBuf,
 };
 
+use brainfuck_rust::{
+    inter::Interpreter,
+    ir,
+    jit::{TapeMode, VM},
+    tape::{DEFAULT_MAX_PAGES, DEFAULT_PAGE_SIZE},
+};
 use clap::Parser;
-use inter::Interpreter;
-use jit::VM;
+
+/// What `--emit` should print instead of running the program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Emit {
+    /// Unoptimized IR, as produced by `ir::compile`
+    Ir,
+    /// IR after `ir::optimize_ir`
+    IrOpt,
+    /// Disassembly of the JIT-generated machine code
+    Asm,
+}
+
+/// Which tape implementation to run the program against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Tape {
+    /// A fixed-size buffer; out-of-range access is an error.
+    Fixed,
+    /// A tape that grows a page at a time as the pointer wanders outside
+    /// the currently allocated range.
+    Paged,
+}
 
 #[derive(Debug, clap::Parser)]
 #[clap(version)]
@@ -21,28 +41,163 @@ struct Opt {
     optimize: bool,
     #[clap(short = 'i', long = "interpreter", help = "Interpreter mode")]
     interpreter: bool,
+    #[clap(
+        long = "emit",
+        value_enum,
+        help = "Dump IR or generated asm instead of executing"
+    )]
+    emit: Option<Emit>,
+    #[clap(
+        long = "max-steps",
+        help = "Abort with an error after this many IR ops (interpreter mode) or loop \
+                back-edges (JIT mode, the default) -- the two engines spend fuel at \
+                different granularities"
+    )]
+    max_steps: Option<u64>,
+    #[clap(
+        long = "timeout-ms",
+        help = "Abort with an error after this many milliseconds"
+    )]
+    timeout_ms: Option<u64>,
+    #[clap(
+        long = "tape",
+        value_enum,
+        default_value = "paged",
+        help = "Tape implementation to run against"
+    )]
+    tape: Tape,
+    #[clap(long = "page-size", help = "Page size in bytes, for --tape paged")]
+    page_size: Option<usize>,
+    #[clap(
+        long = "max-pages",
+        help = "Maximum number of pages to allocate, for --tape paged"
+    )]
+    max_pages: Option<usize>,
+}
+
+fn dump_ir(file_path: &std::path::Path, emit: Emit) -> std::io::Result<()> {
+    let src = std::fs::read_to_string(file_path)?;
+    let mut code = ir::compile(&src).unwrap_or_else(|e| {
+        eprintln!("bfjit: Compile: {}", e);
+        std::process::exit(1);
+    });
+    if emit == Emit::IrOpt {
+        ir::optimize_ir(&mut code);
+    }
+    for (i, ir) in code.iter().enumerate() {
+        println!("{:>5}: {:?}", i, ir);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "disasm")]
+fn dump_asm(file_path: &std::path::Path, optimize: bool, paged: bool) -> std::io::Result<()> {
+    use brainfuck_rust::disasm;
+
+    let src = std::fs::read_to_string(file_path)?;
+    let mut code = ir::compile(&src).unwrap_or_else(|e| {
+        eprintln!("bfjit: Compile: {}", e);
+        std::process::exit(1);
+    });
+    if optimize {
+        ir::optimize_ir(&mut code);
+    }
+
+    let (buf, start, boundaries) = VM::compile_annotated(&code, paged).unwrap_or_else(|e| {
+        eprintln!("bfjit: {}", e);
+        std::process::exit(1);
+    });
+    let base = buf.ptr(start) as u64;
+    for insn in disasm::disassemble(&buf, base, &boundaries) {
+        match insn.ir {
+            Some(ir) => println!("{:#010x}: {:<28} ; {:?}", insn.address, insn.text, ir),
+            None => println!("{:#010x}: {}", insn.address, insn.text),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "disasm"))]
+fn dump_asm(_file_path: &std::path::Path, _optimize: bool, _paged: bool) -> std::io::Result<()> {
+    eprintln!("bfjit: --emit asm requires the `disasm` feature");
+    std::process::exit(1);
+}
+
+impl Opt {
+    fn tape_mode(&self) -> TapeMode {
+        match self.tape {
+            Tape::Fixed => TapeMode::Fixed,
+            Tape::Paged => TapeMode::Paged {
+                page_size: self.page_size.unwrap_or(DEFAULT_PAGE_SIZE),
+                max_pages: self.max_pages.unwrap_or(DEFAULT_MAX_PAGES),
+            },
+        }
+    }
 }
 
 fn main() {
     let opt = Opt::parse();
 
+    let tape_mode = opt.tape_mode();
+
+    if let Some(emit) = opt.emit {
+        let paged = matches!(tape_mode, TapeMode::Paged { .. });
+        let ret = match emit {
+            Emit::Ir | Emit::IrOpt => dump_ir(&opt.file_path, emit),
+            Emit::Asm => dump_asm(&opt.file_path, opt.optimize, paged),
+        };
+        if let Err(ref e) = ret {
+            eprintln!("bfjit: {}", e);
+        }
+        std::process::exit(ret.is_err() as i32);
+    }
+
     let stdin = stdin();
     let stdout = stdout();
+    let timeout = opt.timeout_ms.map(std::time::Duration::from_millis);
 
     let ret = if opt.interpreter {
-        Interpreter::new(
-            &opt.file_path,
-            Box::new(stdin.lock()),
-            Box::new(stdout.lock()),
-            opt.optimize,
-        )
-        .and_then(|mut vm| vm.run())
+        (match tape_mode {
+            TapeMode::Fixed => Interpreter::new(
+                &opt.file_path,
+                Box::new(stdin.lock()),
+                Box::new(stdout.lock()),
+                opt.optimize,
+                opt.max_steps,
+            ),
+            TapeMode::Paged {
+                page_size,
+                max_pages,
+            } => std::fs::read_to_string(&opt.file_path)
+                .map_err(brainfuck_rust::error::VMError::from)
+                .and_then(|src| {
+                    Interpreter::with_paged_tape(
+                        &src,
+                        page_size,
+                        max_pages,
+                        Box::new(stdin.lock()),
+                        Box::new(stdout.lock()),
+                        opt.optimize,
+                        opt.max_steps,
+                    )
+                }),
+        })
+        .and_then(|mut vm| {
+            if let Some(timeout) = timeout {
+                let deadline = std::time::Instant::now() + timeout;
+                vm.set_tick(move || std::time::Instant::now() >= deadline);
+            }
+            vm.run()
+        })
     } else {
         VM::new(
             &opt.file_path,
             Box::new(stdin.lock()),
             Box::new(stdout.lock()),
             opt.optimize,
+            opt.max_steps,
+            timeout,
+            tape_mode,
         )
         .and_then(|mut vm| vm.run())
     };
@@ -0,0 +1,146 @@
+//! Tape storage for the interpreter. `Memory::Fixed` is the original
+//! fixed-size buffer; `Memory::Paged` backs a tape that can grow in both
+//! directions from the origin, allocating fixed-size pages on demand so
+//! programs that wander far from cell 0 don't have to pre-allocate (or
+//! hit `PointerOverflow`) for the whole span they touch.
+
+use alloc::{boxed::Box, collections::BTreeMap, vec, vec::Vec};
+
+use crate::error::RuntimeError;
+
+/// Where a [`crate::inter::Interpreter`]'s fixed-size tape lives: either a
+/// heap allocation it owns (the default, needs the `alloc` feature) or a
+/// fixed buffer supplied by an embedder that doesn't want the crate to
+/// allocate.
+pub enum Tape<'a> {
+    #[cfg(feature = "alloc")]
+    Owned(Box<[u8]>),
+    Borrowed(&'a mut [u8]),
+}
+
+impl core::ops::Deref for Tape<'_> {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        match self {
+            #[cfg(feature = "alloc")]
+            Tape::Owned(mem) => mem,
+            Tape::Borrowed(mem) => mem,
+        }
+    }
+}
+
+impl core::ops::DerefMut for Tape<'_> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        match self {
+            #[cfg(feature = "alloc")]
+            Tape::Owned(mem) => mem,
+            Tape::Borrowed(mem) => mem,
+        }
+    }
+}
+
+pub const DEFAULT_PAGE_SIZE: usize = 64 * 1024;
+pub const DEFAULT_MAX_PAGES: usize = 16 * 1024; // 1 GiB at the default page size
+
+/// A sparse, bidirectionally-unbounded tape: pages are allocated lazily
+/// the first time a cell inside them is written (reading a cell of a page
+/// that was never written yields 0 and allocates nothing), so `ptr` can wander
+/// arbitrarily far left or right of the origin without pre-allocating
+/// anything in between.
+pub struct PagedTape {
+    pages: BTreeMap<i64, Vec<u8>>,
+    page_size: usize,
+    max_pages: usize,
+}
+
+impl PagedTape {
+    pub fn new(page_size: usize, max_pages: usize) -> Self {
+        Self {
+            pages: BTreeMap::new(),
+            page_size,
+            max_pages,
+        }
+    }
+
+    fn page_index(&self, pos: i64) -> i64 {
+        pos.div_euclid(self.page_size as i64)
+    }
+
+    fn page_offset(&self, pos: i64) -> usize {
+        pos.rem_euclid(self.page_size as i64) as usize
+    }
+
+    pub fn get(&self, pos: i64) -> u8 {
+        self.pages
+            .get(&self.page_index(pos))
+            .map_or(0, |page| page[self.page_offset(pos)])
+    }
+
+    pub fn set(&mut self, pos: i64, value: u8) -> Result<(), RuntimeError> {
+        *self.get_mut(pos)? = value;
+        Ok(())
+    }
+
+    pub fn get_mut(&mut self, pos: i64) -> Result<&mut u8, RuntimeError> {
+        let index = self.page_index(pos);
+        if !self.pages.contains_key(&index) {
+            if self.pages.len() >= self.max_pages {
+                return Err(RuntimeError::PointerOverflow);
+            }
+            self.pages.insert(index, vec![0; self.page_size]);
+        }
+        let offset = self.page_offset(pos);
+        Ok(&mut self.pages.get_mut(&index).expect("just inserted")[offset])
+    }
+}
+
+/// The interpreter's tape: a classic fixed-size buffer, or a [`PagedTape`]
+/// that grows on demand. Selected per-run via `--tape paged`/`--tape fixed`.
+pub enum Memory<'a> {
+    Fixed(Tape<'a>),
+    Paged(PagedTape),
+}
+
+impl Memory<'_> {
+    pub fn get(&self, ptr: i64) -> u8 {
+        match self {
+            Memory::Fixed(tape) => tape[ptr as usize],
+            Memory::Paged(paged) => paged.get(ptr),
+        }
+    }
+
+    pub fn set(&mut self, ptr: i64, value: u8) -> Result<(), RuntimeError> {
+        match self {
+            Memory::Fixed(tape) => {
+                tape[ptr as usize] = value;
+                Ok(())
+            }
+            Memory::Paged(paged) => paged.set(ptr, value),
+        }
+    }
+
+    pub fn get_mut(&mut self, ptr: i64) -> Result<&mut u8, RuntimeError> {
+        match self {
+            Memory::Fixed(tape) => Ok(&mut tape[ptr as usize]),
+            Memory::Paged(paged) => paged.get_mut(ptr),
+        }
+    }
+
+    /// Move `ptr` by `delta`, bounds-checking eagerly for a fixed tape (the
+    /// only point it *can* be checked, since it has no fault handler) or
+    /// letting it move freely for a paged tape (bounds are enforced lazily,
+    /// the first time a cell in a new page is actually written).
+    pub fn move_ptr(&self, ptr: i64, delta: i64) -> Result<i64, RuntimeError> {
+        let new_ptr = ptr + delta;
+        match self {
+            Memory::Fixed(tape) => {
+                if new_ptr < 0 || new_ptr as usize >= tape.len() {
+                    Err(RuntimeError::PointerOverflow)
+                } else {
+                    Ok(new_ptr)
+                }
+            }
+            Memory::Paged(_) => Ok(new_ptr),
+        }
+    }
+}